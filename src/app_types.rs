@@ -1,10 +1,12 @@
 use axum::{
-    extract::FromRequest,
+    extract::{FromRequest, Request},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 
+use crate::jsonrpc::{JsonRpcReplyBatch, RpcError};
+
 pub type AppResult<T> = Result<AppJson<T>, AppError>;
 
 #[derive(FromRequest)]
@@ -20,6 +22,34 @@ where
     }
 }
 
+/// Extracts a JSON-RPC request body, like `AppJson`, but a malformed body
+/// answers with a spec-compliant `-32700` parse-error envelope over HTTP 200
+/// instead of falling through to `AppError`'s ad-hoc HTTP 500 — JSON-RPC
+/// clients expect every reply, including parse failures, in this shape.
+pub struct JsonRpcBody<T>(pub T);
+
+impl<S, T> FromRequest<S> for JsonRpcBody<T>
+where
+    S: Send + Sync,
+    axum::Json<T>: FromRequest<S>,
+    <axum::Json<T> as FromRequest<S>>::Rejection: std::fmt::Display,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::<T>::from_request(req, state).await {
+            Ok(axum::Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let error = RpcError::new(RpcError::PARSE_ERROR, rejection.to_string());
+                Err(AppJson(JsonRpcReplyBatch::<serde_json::Value>::Single(
+                    error.into_reply(None),
+                ))
+                .into_response())
+            }
+        }
+    }
+}
+
 pub struct AppError(pub anyhow::Error);
 
 impl IntoResponse for AppError {
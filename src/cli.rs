@@ -1,6 +1,6 @@
 use structopt::StructOpt;
 
-use crate::signers::{aws_kms::AwsOpt, yubihsm::YubiOpt};
+use crate::signers::{aws_kms::AwsOpt, ledger::LedgerOpt, yubihsm::YubiOpt};
 
 #[derive(StructOpt)]
 pub struct Opt {
@@ -12,4 +12,5 @@ pub struct Opt {
 pub enum Command {
     Yubihsm(YubiOpt),
     AwsKms(AwsOpt),
+    Ledger(LedgerOpt),
 }
@@ -11,7 +11,8 @@ pub struct JsonRpcRequest<T> {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JsonRpcReply<T> {
-    pub id: u64,
+    /// `null` for errors raised before a request `id` could be read, per spec.
+    pub id: Option<u64>,
     pub jsonrpc: String,
     #[serde(flatten)]
     pub result: JsonRpcResult<T>,
@@ -24,7 +25,111 @@ pub enum JsonRpcResult<T> {
     Error { code: i64, message: String },
 }
 
+/// A JSON-RPC 2.0 error, carrying one of the standard reserved codes.
+#[derive(Clone, Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// Not part of the spec's reserved range; used here for signer/HSM
+    /// failures, matching the convention real JSON-RPC servers use for the
+    /// "server error" range (-32000 to -32099).
+    pub const SIGNER_ERROR: i64 = -32000;
+    /// A narrower `SIGNER_ERROR`: specifically a failure signing a digest
+    /// directly against the underlying hardware/KMS key (`sign_hash`), as
+    /// opposed to a downstream RPC/tx-building failure that also reports as
+    /// `SIGNER_ERROR`. Callers that fail over on signer health (e.g. the
+    /// YubiHSM connector pool) should key off this code rather than
+    /// `SIGNER_ERROR`, so an `eth_estimateGas` revert or a malformed
+    /// transaction doesn't get mistaken for a dead device.
+    pub const HSM_ERROR: i64 = -32001;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(Self::INVALID_REQUEST, message)
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self::new(Self::METHOD_NOT_FOUND, format!("method not found: {method}"))
+    }
+
+    pub fn invalid_params(message: impl std::fmt::Display) -> Self {
+        Self::new(Self::INVALID_PARAMS, message.to_string())
+    }
+
+    pub fn signer_error(message: impl std::fmt::Display) -> Self {
+        Self::new(Self::SIGNER_ERROR, message.to_string())
+    }
+
+    pub fn hsm_error(message: impl std::fmt::Display) -> Self {
+        Self::new(Self::HSM_ERROR, message.to_string())
+    }
+
+    pub fn into_reply<T>(self, id: Option<u64>) -> JsonRpcReply<T> {
+        JsonRpcReply {
+            id,
+            jsonrpc: "2.0".to_string(),
+            result: JsonRpcResult::Error {
+                code: self.code,
+                message: self.message,
+            },
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::signer_error(err)
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AddressResponse {
     pub address: String,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PubkeyResponse {
+    pub compressed: String,
+    pub uncompressed: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerifyRequest {
+    pub digest: String,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+}
+
+/// JSON-RPC 2.0 clients may POST either a single request object or a batch
+/// array of them to the same route.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcRequestBatch<T> {
+    Single(JsonRpcRequest<T>),
+    Batch(Vec<JsonRpcRequest<T>>),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonRpcReplyBatch<T> {
+    Single(JsonRpcReply<T>),
+    Batch(Vec<JsonRpcReply<T>>),
+}
@@ -7,7 +7,7 @@ mod signers;
 use cli::{Command, Opt};
 use structopt::StructOpt;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use signers::{aws_kms::handle_aws_kms, yubihsm::handle_yubihsm};
+use signers::{aws_kms::handle_aws_kms, ledger::handle_ledger, yubihsm::handle_yubihsm};
 
 #[tokio::main]
 async fn main() {
@@ -29,5 +29,8 @@ async fn main() {
         Command::AwsKms(aws_opt) => {
             handle_aws_kms(aws_opt).await;
         },
+        Command::Ledger(ledger_opt) => {
+            handle_ledger(ledger_opt).await;
+        },
     }
 }
@@ -1,11 +1,14 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use alloy::network::EthereumWallet;
-use alloy::primitives::Address;
-use alloy::signers::{aws::AwsSigner, Signer};
+use alloy::primitives::{hex, Address, Signature, B256};
+use alloy::signers::{
+    aws::AwsSigner,
+    k256::{ecdsa::VerifyingKey, pkcs8::DecodePublicKey},
+    Signer,
+};
 use anyhow::Result as AnyhowResult;
 use aws_config::BehaviorVersion;
-use aws_sdk_kms::Client;
+use aws_sdk_kms::config::Region;
 use axum::http::StatusCode;
 use axum::routing::get;
 use axum::Json;
@@ -22,16 +25,56 @@ use tokio::sync::Mutex;
 use tower_http::{timeout::TimeoutLayer, trace::TraceLayer};
 use tracing::info;
 
-use crate::jsonrpc::AddressResponse;
+use crate::jsonrpc::{AddressResponse, PubkeyResponse, VerifyRequest, VerifyResponse};
 use crate::{
-    app_types::{AppJson, AppResult},
-    jsonrpc::{JsonRpcReply, JsonRpcRequest},
+    app_types::{AppJson, JsonRpcBody},
+    jsonrpc::{JsonRpcReplyBatch, JsonRpcRequest, JsonRpcRequestBatch},
     shutdown_signal::shutdown_signal,
-    signers::common::handle_eth_sign_jsonrpc,
+    signers::common::{handle_eth_sign_jsonrpc_batch, signer_unavailable_reply},
+    signers::kms_pool::KmsClientPool,
+    signers::rpc_fill::{GasOracleConfig, RpcFiller},
 };
 
 #[derive(StructOpt)]
 pub struct AwsOpt {
+    /// Upstream JSON-RPC URL used to auto-fill nonce and chainId (and, with
+    /// `--gas-oracle`, gas fields) on transactions that leave them unset
+    #[structopt(long = "rpc-url", env = "AWS_KMS_RPC_URL")]
+    pub rpc_url: Option<String>,
+
+    /// EIP-155 chain id to bind signatures to
+    #[structopt(long = "chain-id", env = "AWS_KMS_CHAIN_ID")]
+    pub chain_id: Option<u64>,
+
+    /// Auto-fill missing gas/fee fields from the upstream RPC's
+    /// `eth_estimateGas`/`eth_feeHistory`-backed estimates before signing
+    #[structopt(long = "gas-oracle")]
+    pub gas_oracle: bool,
+
+    /// Multiplier applied to gas-oracle estimates, to pad for inclusion
+    #[structopt(long = "gas-multiplier", default_value = "1.0")]
+    pub gas_multiplier: f64,
+
+    /// Upper bound applied to gas-oracle fee estimates (wei), to bound spend
+    #[structopt(long = "gas-cap")]
+    pub gas_cap: Option<u128>,
+
+    /// KMS region(s) to fail over across, in priority order. Repeat the flag
+    /// or pass a comma-separated list. Defaults to the environment's region
+    /// if omitted
+    #[structopt(long = "region", env = "AWS_KMS_REGIONS", use_delimiter = true)]
+    pub regions: Vec<String>,
+
+    /// Number of regions that must agree on a key's address before a
+    /// signature is served from it
+    #[structopt(long = "kms-quorum", default_value = "1")]
+    pub kms_quorum: usize,
+
+    /// Per-region timeout for a KMS call before failing over to the next
+    /// region, in milliseconds
+    #[structopt(long = "kms-timeout-ms", default_value = "2000")]
+    pub kms_timeout_ms: u64,
+
     #[structopt(subcommand)] // Note that we mark a field as a subcommand
     pub cmd: AwsCommand,
 }
@@ -43,8 +86,10 @@ pub enum AwsCommand {
 
 #[derive(Clone)]
 struct AppState {
-    client: Client,
-    signers: Arc<Mutex<HashMap<String, EthereumWallet>>>,
+    kms_pool: Arc<KmsClientPool>,
+    chain_id: Option<u64>,
+    signers: Arc<Mutex<HashMap<String, AwsSigner>>>,
+    rpc_filler: Option<Arc<RpcFiller>>,
 }
 
 const API_TIMEOUT_SECS: u64 = 30;
@@ -58,25 +103,28 @@ async fn handle_ping() -> &'static str {
 async fn handle_request(
     Path(key_id): Path<String>,
     State(state): State<Arc<AppState>>,
-    AppJson(payload): AppJson<JsonRpcRequest<Vec<Value>>>,
-) -> AppResult<JsonRpcReply<Value>> {
-    let eth_signer = get_signer(state.clone(), key_id).await?;
-    handle_eth_sign_jsonrpc(payload, eth_signer).await
+    JsonRpcBody(batch): JsonRpcBody<JsonRpcRequestBatch<Vec<Value>>>,
+) -> AppJson<JsonRpcReplyBatch<Value>> {
+    let signer = match get_signer(state.clone(), key_id).await {
+        Ok(signer) => signer,
+        Err(err) => return signer_unavailable_reply(&batch, &err),
+    };
+
+    handle_eth_sign_jsonrpc_batch(batch, signer, state.rpc_filler.as_deref()).await
 }
 
-async fn get_signer(state: Arc<AppState>, key_id: String) -> AnyhowResult<EthereumWallet> {
+async fn get_signer(state: Arc<AppState>, key_id: String) -> AnyhowResult<AwsSigner> {
     let mut signers = state.signers.lock().await;
 
     if let Some(signer) = signers.get(&key_id) {
         return Ok(signer.clone());
     }
 
-    let signer = AwsSigner::new(state.client.clone(), key_id.clone(), None).await?;
-    let eth_signer = EthereumWallet::from(signer);
+    let signer = state.kms_pool.signer(&key_id, state.chain_id).await?;
 
-    signers.insert(key_id.clone(), eth_signer.clone());
+    signers.insert(key_id.clone(), signer.clone());
 
-    Ok(eth_signer)
+    Ok(signer)
 }
 
 #[debug_handler]
@@ -94,26 +142,125 @@ async fn handle_address_request(
 }
 
 async fn get_address(state: Arc<AppState>, key_id: String) -> AnyhowResult<Address> {
-    let signer = AwsSigner::new(state.client.clone(), key_id.clone(), None).await?;
+    let signer = state.kms_pool.signer(&key_id, state.chain_id).await?;
 
     Ok(signer.address())
 }
 
-pub async fn handle_aws_kms(opt: AwsOpt) {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_kms::Client::new(&config);
+/// Fetches the DER-encoded SPKI public key KMS holds for `key_id`, the same
+/// call `AwsSigner::new` makes internally to derive the signer's address.
+async fn get_verifying_key(state: Arc<AppState>, key_id: &str) -> AnyhowResult<VerifyingKey> {
+    let der = state.kms_pool.get_public_key_der(key_id).await?;
+
+    Ok(VerifyingKey::from_public_key_der(&der)?)
+}
+
+#[debug_handler]
+async fn handle_pubkey_request(
+    Path(key_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<PubkeyResponse>, StatusCode> {
+    let verifying_key = get_verifying_key(state.clone(), &key_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    Ok(Json(PubkeyResponse {
+        compressed: hex::encode_prefixed(verifying_key.to_encoded_point(true).as_bytes()),
+        uncompressed: hex::encode_prefixed(verifying_key.to_encoded_point(false).as_bytes()),
+    }))
+}
+
+/// Recovers the signer address from `(digest, signature)` and reports
+/// whether it matches this key's address, without ever touching KMS
+/// private-key material.
+#[debug_handler]
+async fn handle_verify_request(
+    Path(key_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    AppJson(payload): AppJson<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, StatusCode> {
+    let address = get_address(state.clone(), key_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let digest: B256 = payload
+        .digest
+        .parse()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let signature: Signature = payload
+        .signature
+        .parse()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let valid = signature
+        .recover_address_from_prehash(&digest)
+        .map(|recovered| recovered == address)
+        .unwrap_or(false);
+
+    Ok(Json(VerifyResponse { valid }))
+}
+
+/// Builds one KMS client per configured `--region`, or a single client from
+/// the environment's default region if none were given.
+async fn build_kms_clients(opt: &AwsOpt) -> Vec<(String, aws_sdk_kms::Client)> {
+    if opt.regions.is_empty() {
+        let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+        let region = config
+            .region()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "default".to_string());
+
+        return vec![(region, aws_sdk_kms::Client::new(&config))];
+    }
+
+    let mut clients = Vec::with_capacity(opt.regions.len());
+    for region in &opt.regions {
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(region.clone()))
+            .load()
+            .await;
+
+        clients.push((region.clone(), aws_sdk_kms::Client::new(&config)));
+    }
+
+    clients
+}
+
+pub async fn handle_aws_kms(opt: AwsOpt) {
     match opt.cmd {
         AwsCommand::Serve => {
+            let gas_oracle = opt.gas_oracle.then_some(GasOracleConfig {
+                multiplier: opt.gas_multiplier,
+                cap: opt.gas_cap,
+            });
+            let rpc_filler = opt
+                .rpc_url
+                .as_deref()
+                .map(|rpc_url| RpcFiller::new(rpc_url, gas_oracle))
+                .transpose()
+                .unwrap()
+                .map(Arc::new);
+
+            let kms_pool = Arc::new(KmsClientPool::new(
+                build_kms_clients(&opt).await,
+                Duration::from_millis(opt.kms_timeout_ms),
+                opt.kms_quorum,
+            ));
+            kms_pool.spawn_health_check();
+
             let shared_state = Arc::new(AppState {
-                client,
+                kms_pool,
+                chain_id: opt.chain_id,
                 signers: Arc::new(Mutex::new(HashMap::new())),
+                rpc_filler,
             });
 
             let app = Router::new()
                 .route("/ping", get(handle_ping))
                 .route("/key/:key_id", post(handle_request))
                 .route("/key/:key_id/address", get(handle_address_request))
+                .route("/key/:key_id/pubkey", get(handle_pubkey_request))
+                .route("/key/:key_id/verify", post(handle_verify_request))
                 .with_state(shared_state)
                 .layer((
                     TraceLayer::new_for_http(),
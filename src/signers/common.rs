@@ -1,73 +1,344 @@
 use alloy::{
+    dyn_abi::TypedData,
     hex,
-    network::{EthereumWallet, TransactionBuilder},
+    network::{EthereumWallet, TransactionBuilder, TxSigner},
+    primitives::{keccak256, Address, Signature, TxHash},
     rlp::Encodable,
     rpc::types::TransactionRequest,
+    signers::Signer,
 };
-use anyhow::{anyhow, Result as AnyhowResult};
 use serde_json::Value;
 
 use crate::{
-    app_types::{AppError, AppJson, AppResult},
-    jsonrpc::{JsonRpcReply, JsonRpcRequest, JsonRpcResult},
+    app_types::AppJson,
+    jsonrpc::{
+        JsonRpcReply, JsonRpcReplyBatch, JsonRpcRequest, JsonRpcRequestBatch, JsonRpcResult,
+        RpcError,
+    },
+    signers::rpc_fill::RpcFiller,
 };
 
-pub async fn handle_eth_sign_transaction(
+/// Bound shared by every backend's cached signer (YubiHSM, AWS KMS, ...): it
+/// must be able to build an `EthereumWallet` for transaction signing and to
+/// sign raw digests directly, which transaction signing alone doesn't need.
+pub trait EthSigner: Signer + TxSigner<Signature> + Clone + Send + Sync + 'static {}
+
+impl<S> EthSigner for S where S: Signer + TxSigner<Signature> + Clone + Send + Sync + 'static {}
+
+pub async fn handle_eth_sign_transaction<S: EthSigner>(
     payload: JsonRpcRequest<Vec<Value>>,
-    signer: EthereumWallet,
-) -> AnyhowResult<JsonRpcReply<Value>> {
-    let params = payload.params.ok_or_else(|| anyhow!("params is empty"))?;
+    signer: &S,
+    rpc_filler: Option<&RpcFiller>,
+) -> Result<JsonRpcReply<Value>, RpcError> {
+    let params = payload
+        .params
+        .filter(|params| !params.is_empty())
+        .ok_or_else(|| RpcError::invalid_params("params is empty"))?;
 
-    if params.is_empty() {
-        return Err(anyhow!("params is empty"));
+    let wallet = EthereumWallet::from(signer.clone());
+    let tx_object = params[0].clone();
+    let mut tx_request = serde_json::from_value::<TransactionRequest>(tx_object)
+        .map_err(RpcError::invalid_params)?;
+
+    if let Some(filler) = rpc_filler {
+        filler
+            .fill(signer.address(), &mut tx_request)
+            .await
+            .map_err(RpcError::signer_error)?;
     }
 
-    let tx_object = params[0].clone();
-    let tx_request = serde_json::from_value::<TransactionRequest>(tx_object)?;
-    let tx_envelope = tx_request.build(&signer).await?;
-    println!("tx_envelope: {:?}", tx_envelope.tx_type());
-    println!("tx_envelope: {:?}", tx_envelope);
-    tx_envelope.signature_hash();
-
-    let mut encoded_tx = vec![];
-    encoded_tx.push(tx_envelope.tx_type() as u8);
+    let tx_envelope = tx_request
+        .build(&wallet)
+        .await
+        .map_err(RpcError::signer_error)?;
+
+    let mut encoded_tx = vec![tx_envelope.tx_type() as u8];
     tx_envelope.encode(&mut encoded_tx);
-    println!("encoded_tx: {:?}", encoded_tx);
     let rlp_hex = hex::encode_prefixed(encoded_tx);
 
-    println!("rlp_hex: {:?}", rlp_hex);
-
     Ok(JsonRpcReply {
-        id: payload.id,
+        id: Some(payload.id),
         jsonrpc: payload.jsonrpc,
         result: JsonRpcResult::Result(rlp_hex.into()),
     })
 }
 
+/// `eth_sendTransaction`: like `eth_signTransaction`, but nonces are handed
+/// out from `rpc_filler`'s in-memory counter rather than left for the caller
+/// to manage, and the signed transaction is forwarded on via
+/// `eth_sendRawTransaction`. If the node rejects it for a stale nonce, the
+/// counter is resynced from the chain and the send retried once; any other
+/// failure after a nonce was auto-allocated releases it back to the counter
+/// so a transient error (RPC hiccup, build failure, revert-on-send) doesn't
+/// leave a permanent gap that stalls every later send from this key.
+pub async fn handle_eth_send_transaction<S: EthSigner>(
+    payload: JsonRpcRequest<Vec<Value>>,
+    signer: &S,
+    rpc_filler: &RpcFiller,
+) -> Result<JsonRpcReply<Value>, RpcError> {
+    let params = payload
+        .params
+        .filter(|params| !params.is_empty())
+        .ok_or_else(|| RpcError::invalid_params("params is empty"))?;
+
+    let wallet = EthereumWallet::from(signer.clone());
+    let tx_object = params[0].clone();
+    let mut tx_request = serde_json::from_value::<TransactionRequest>(tx_object)
+        .map_err(RpcError::invalid_params)?;
+
+    let explicit_nonce = tx_request.nonce();
+    let mut retried = false;
+
+    loop {
+        let nonce = match explicit_nonce {
+            Some(nonce) => nonce,
+            None => rpc_filler
+                .next_nonce(signer.address())
+                .await
+                .map_err(RpcError::signer_error)?,
+        };
+        tx_request.set_nonce(nonce);
+
+        match handle_eth_send_transaction_attempt(rpc_filler, &wallet, signer, &mut tx_request).await {
+            Ok(tx_hash) => {
+                return Ok(JsonRpcReply {
+                    id: Some(payload.id),
+                    jsonrpc: payload.jsonrpc,
+                    result: JsonRpcResult::Result(tx_hash.to_string().into()),
+                })
+            }
+            Err(err) if !retried && explicit_nonce.is_none() && is_nonce_too_low(&err) => {
+                retried = true;
+                rpc_filler
+                    .resync_nonce(signer.address())
+                    .await
+                    .map_err(RpcError::signer_error)?;
+            }
+            Err(err) => {
+                if explicit_nonce.is_none() {
+                    rpc_filler.release_nonce(signer.address(), nonce).await;
+                }
+
+                return Err(RpcError::signer_error(err));
+            }
+        }
+    }
+}
+
+/// Fills, signs and broadcasts `tx_request` once, for a single attempt of
+/// the retry loop above.
+async fn handle_eth_send_transaction_attempt<S: EthSigner>(
+    rpc_filler: &RpcFiller,
+    wallet: &EthereumWallet,
+    signer: &S,
+    tx_request: &mut TransactionRequest,
+) -> anyhow::Result<TxHash> {
+    rpc_filler.fill(signer.address(), tx_request).await?;
+
+    let tx_envelope = tx_request.clone().build(wallet).await?;
+
+    let mut encoded_tx = vec![tx_envelope.tx_type() as u8];
+    tx_envelope.encode(&mut encoded_tx);
+
+    rpc_filler.send_raw_transaction(&encoded_tx).await
+}
+
+fn is_nonce_too_low(err: &anyhow::Error) -> bool {
+    let message = err.to_string().to_lowercase();
+    message.contains("nonce too low") || message.contains("nonce is too low")
+}
+
+/// `eth_signTypedData_v4`: `params = [address, typedData]`. `TypedData`
+/// already implements the full EIP-712 `encodeType`/`encodeData`/`hashStruct`
+/// machinery, so we only need to hash it and sign the resulting digest with
+/// the underlying signer.
+pub async fn handle_eth_sign_typed_data<S: EthSigner>(
+    payload: JsonRpcRequest<Vec<Value>>,
+    signer: &S,
+) -> Result<JsonRpcReply<Value>, RpcError> {
+    let params = payload
+        .params
+        .ok_or_else(|| RpcError::invalid_params("params is empty"))?;
+
+    if params.len() < 2 {
+        return Err(RpcError::invalid_params(
+            "eth_signTypedData_v4 expects params = [address, typedData]",
+        ));
+    }
+
+    let typed_data =
+        serde_json::from_value::<TypedData>(params[1].clone()).map_err(RpcError::invalid_params)?;
+    let digest = typed_data
+        .eip712_signing_hash()
+        .map_err(RpcError::invalid_params)?;
+    let signature = signer
+        .sign_hash(&digest)
+        .await
+        .map_err(RpcError::hsm_error)?;
+
+    Ok(JsonRpcReply {
+        id: Some(payload.id),
+        jsonrpc: payload.jsonrpc,
+        result: JsonRpcResult::Result(hex::encode_prefixed(signature.as_bytes()).into()),
+    })
+}
+
+/// `personal_sign`/`eth_sign` params are conventionally `[message, address]`
+/// and `[address, message]` respectively; find the message by elimination
+/// rather than trusting method-specific ordering, since wallets aren't
+/// consistent about it.
+fn message_param(params: &[Value]) -> Result<&Value, RpcError> {
+    if params.len() < 2 {
+        return Err(RpcError::invalid_params(
+            "expected params = [message, address] (or reverse)",
+        ));
+    }
+
+    let is_address = |value: &Value| {
+        value
+            .as_str()
+            .map(|s| s.parse::<Address>().is_ok())
+            .unwrap_or(false)
+    };
+
+    match (is_address(&params[0]), is_address(&params[1])) {
+        (true, false) => Ok(&params[1]),
+        _ => Ok(&params[0]),
+    }
+}
+
+/// Hex-decodes `0x`-prefixed input; anything else is signed as literal UTF-8
+/// bytes, since `hex::decode` would otherwise happily (and wrongly) decode a
+/// plain-text message that merely happens to look like hex (`"deadbeef"`).
+fn message_bytes(value: &Value) -> Result<Vec<u8>, RpcError> {
+    let raw = value
+        .as_str()
+        .ok_or_else(|| RpcError::invalid_params("message param must be a string"))?;
+
+    match raw.strip_prefix("0x") {
+        Some(stripped) => hex::decode(stripped).map_err(RpcError::invalid_params),
+        None => Ok(raw.as_bytes().to_vec()),
+    }
+}
+
+/// `personal_sign`/`eth_sign`: hash the message with the
+/// `"\x19Ethereum Signed Message:\n" + len` prefix and sign the digest.
+pub async fn handle_personal_sign<S: EthSigner>(
+    payload: JsonRpcRequest<Vec<Value>>,
+    signer: &S,
+) -> Result<JsonRpcReply<Value>, RpcError> {
+    let params = payload
+        .params
+        .ok_or_else(|| RpcError::invalid_params("params is empty"))?;
+    let message = message_bytes(message_param(&params)?)?;
+
+    let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+    prefixed.extend_from_slice(&message);
+    let digest = keccak256(prefixed);
+    let signature = signer
+        .sign_hash(&digest)
+        .await
+        .map_err(RpcError::hsm_error)?;
+
+    Ok(JsonRpcReply {
+        id: Some(payload.id),
+        jsonrpc: payload.jsonrpc,
+        result: JsonRpcResult::Result(hex::encode_prefixed(signature.as_bytes()).into()),
+    })
+}
+
 pub async fn handle_health_status(
     payload: JsonRpcRequest<Vec<Value>>,
-) -> AnyhowResult<JsonRpcReply<Value>> {
+) -> Result<JsonRpcReply<Value>, RpcError> {
     Ok(JsonRpcReply {
-        id: payload.id,
+        id: Some(payload.id),
         jsonrpc: payload.jsonrpc,
         result: JsonRpcResult::Result(env!("CARGO_PKG_VERSION").into()),
     })
 }
 
-pub async fn handle_eth_sign_jsonrpc(
+pub async fn handle_eth_sign_jsonrpc<S: EthSigner>(
     payload: JsonRpcRequest<Vec<Value>>,
-    signer: EthereumWallet,
-) -> AppResult<JsonRpcReply<Value>> {
-    let method = payload.method.as_str();
+    signer: S,
+    rpc_filler: Option<&RpcFiller>,
+) -> AppJson<JsonRpcReply<Value>> {
+    let method = payload.method.clone();
+    let id = payload.id;
 
-    let result = match method {
-        "eth_signTransaction" => handle_eth_sign_transaction(payload, signer).await,
+    let result = match method.as_str() {
+        "eth_signTransaction" => handle_eth_sign_transaction(payload, &signer, rpc_filler).await,
+        "eth_sendTransaction" => match rpc_filler {
+            Some(filler) => handle_eth_send_transaction(payload, &signer, filler).await,
+            None => Err(RpcError::invalid_request(
+                "eth_sendTransaction requires an upstream --rpc-url to be configured",
+            )),
+        },
+        "eth_signTypedData_v4" => handle_eth_sign_typed_data(payload, &signer).await,
+        "personal_sign" | "eth_sign" => handle_personal_sign(payload, &signer).await,
         "health_status" => handle_health_status(payload).await,
-        _ => Err(anyhow!(
-            "method not supported (only eth_signTransaction and health_status): {}",
-            method
-        )),
+        _ => Err(RpcError::method_not_found(&method)),
+    };
+
+    AppJson(result.unwrap_or_else(|err| err.into_reply(Some(id))))
+}
+
+/// Dispatches a single request or, for JSON-RPC 2.0 batches, every request in
+/// the array concurrently, preserving order in the response array.
+pub async fn handle_eth_sign_jsonrpc_batch<S: EthSigner>(
+    batch: JsonRpcRequestBatch<Vec<Value>>,
+    signer: S,
+    rpc_filler: Option<&RpcFiller>,
+) -> AppJson<JsonRpcReplyBatch<Value>> {
+    match batch {
+        JsonRpcRequestBatch::Single(payload) => {
+            let AppJson(reply) = handle_eth_sign_jsonrpc(payload, signer, rpc_filler).await;
+            AppJson(JsonRpcReplyBatch::Single(reply))
+        }
+        JsonRpcRequestBatch::Batch(requests) => {
+            if requests.is_empty() {
+                return AppJson(JsonRpcReplyBatch::Single(
+                    RpcError::invalid_request("batch request must not be empty").into_reply(None),
+                ));
+            }
+
+            let replies = futures::future::join_all(requests.into_iter().map(|payload| {
+                let signer = signer.clone();
+                async move {
+                    let AppJson(reply) =
+                        handle_eth_sign_jsonrpc(payload, signer, rpc_filler).await;
+                    reply
+                }
+            }))
+            .await;
+
+            AppJson(JsonRpcReplyBatch::Batch(replies))
+        }
+    }
+}
+
+/// Builds a signer/HSM-error reply matching `batch`'s shape (single vs
+/// batch) and echoing the id(s)/jsonrpc version of each request, for
+/// failures (e.g. "all connectors unavailable") that occur acquiring a
+/// signer, before a request ever reaches `handle_eth_sign_jsonrpc_batch`.
+/// Keeps such failures inside the JSON-RPC error envelope (HTTP 200) rather
+/// than falling through to `AppError`'s HTTP 500.
+pub fn signer_unavailable_reply(
+    batch: &JsonRpcRequestBatch<Vec<Value>>,
+    err: &anyhow::Error,
+) -> AppJson<JsonRpcReplyBatch<Value>> {
+    let reply_for = |payload: &JsonRpcRequest<Vec<Value>>| -> JsonRpcReply<Value> {
+        JsonRpcReply {
+            jsonrpc: payload.jsonrpc.clone(),
+            ..RpcError::signer_error(err).into_reply(Some(payload.id))
+        }
+    };
+
+    let reply = match batch {
+        JsonRpcRequestBatch::Single(payload) => JsonRpcReplyBatch::Single(reply_for(payload)),
+        JsonRpcRequestBatch::Batch(payloads) => {
+            JsonRpcReplyBatch::Batch(payloads.iter().map(reply_for).collect())
+        }
     };
 
-    result.map(AppJson).map_err(AppError)
+    AppJson(reply)
 }
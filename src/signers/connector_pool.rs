@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use alloy::signers::local::yubihsm::{Client, Connector, Credentials};
+use tracing::{debug, warn};
+
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+
+struct ConnectorEntry {
+    connector: Connector,
+    healthy: AtomicBool,
+}
+
+/// An ordered set of YubiHSM connectors to fail over across when a device or
+/// HTTP connector goes down, with a background task that reconnects failed
+/// entries so the pool self-heals without a restart.
+pub struct ConnectorPool {
+    entries: Vec<ConnectorEntry>,
+    credentials: Credentials,
+}
+
+impl ConnectorPool {
+    pub fn new(connectors: Vec<Connector>, credentials: Credentials) -> Self {
+        Self {
+            entries: connectors
+                .into_iter()
+                .map(|connector| ConnectorEntry {
+                    connector,
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            credentials,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn connector(&self, index: usize) -> Connector {
+        self.entries[index].connector.clone()
+    }
+
+    pub fn mark_unhealthy(&self, index: usize) {
+        if self.entries[index].healthy.swap(false, Ordering::Relaxed) {
+            warn!("YubiHSM connector {index} marked unhealthy");
+        }
+    }
+
+    pub fn mark_healthy(&self, index: usize) {
+        if !self.entries[index].healthy.swap(true, Ordering::Relaxed) {
+            debug!("YubiHSM connector {index} recovered");
+        }
+    }
+
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.entries[index].healthy.load(Ordering::Relaxed)
+    }
+
+    /// Connector indices in priority order, starting from `preferred` (the
+    /// last-known-good connector) and wrapping around the rest of the pool,
+    /// with any connectors the health check has marked unhealthy sorted
+    /// after every healthy one (but still included, as a last resort).
+    pub fn try_order(&self, preferred: Option<usize>) -> Vec<usize> {
+        let len = self.entries.len();
+        let order = match preferred {
+            Some(start) => (0..len).map(|offset| (start + offset) % len).collect(),
+            None => (0..len).collect::<Vec<_>>(),
+        };
+
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) = order
+            .into_iter()
+            .partition(|&index| self.is_healthy(index));
+
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Spawns a task that periodically probes unhealthy connectors by
+    /// opening (and immediately dropping) a session against them, restoring
+    /// any that have recovered.
+    pub fn spawn_health_check(self: &Arc<Self>) {
+        let pool = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS));
+
+            loop {
+                ticker.tick().await;
+
+                for (index, entry) in pool.entries.iter().enumerate() {
+                    if entry.healthy.load(Ordering::Relaxed) {
+                        continue;
+                    }
+
+                    let connector = entry.connector.clone();
+                    let credentials = pool.credentials.clone();
+                    let reconnected =
+                        tokio::task::spawn_blocking(move || Client::open(connector, credentials, false).is_ok())
+                            .await
+                            .unwrap_or(false);
+
+                    if reconnected {
+                        pool.mark_healthy(index);
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use alloy::signers::{aws::AwsSigner, Signer};
+use anyhow::{anyhow, Result as AnyhowResult};
+use aws_sdk_kms::Client;
+use tracing::{debug, info, warn};
+
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+
+struct ClientEntry {
+    region: String,
+    client: Client,
+    healthy: AtomicBool,
+}
+
+/// An ordered set of regional KMS clients to fail over across when a
+/// region-local outage would otherwise take the whole proxy down.
+///
+/// KMS/ECDSA signatures are non-deterministic, so byte-for-byte agreement
+/// across regions isn't meaningful; `quorum` instead requires that many
+/// regions agree on the *address* a key resolves to before a signer is
+/// handed back, which still catches a misreplicated or misconfigured key in
+/// one region.
+pub struct KmsClientPool {
+    entries: Vec<ClientEntry>,
+    attempt_timeout: Duration,
+    quorum: usize,
+}
+
+impl KmsClientPool {
+    pub fn new(clients: Vec<(String, Client)>, attempt_timeout: Duration, quorum: usize) -> Self {
+        Self {
+            entries: clients
+                .into_iter()
+                .map(|(region, client)| ClientEntry {
+                    region,
+                    client,
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            attempt_timeout,
+            quorum: quorum.max(1),
+        }
+    }
+
+    fn mark_unhealthy(&self, index: usize) {
+        if self.entries[index].healthy.swap(false, Ordering::Relaxed) {
+            warn!(region = %self.entries[index].region, "KMS region marked unhealthy");
+        }
+    }
+
+    fn mark_healthy(&self, index: usize) {
+        if !self.entries[index].healthy.swap(true, Ordering::Relaxed) {
+            info!(region = %self.entries[index].region, "KMS region recovered");
+        }
+    }
+
+    /// Entry indices in priority order, with any region the health check has
+    /// marked unhealthy sorted after every healthy one (but still included,
+    /// as a last resort if every region is currently down).
+    fn try_order(&self) -> Vec<usize> {
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) = (0..self.entries.len())
+            .partition(|&index| self.entries[index].healthy.load(Ordering::Relaxed));
+
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    /// Spawns a task that periodically probes unhealthy regions with a
+    /// lightweight `ListKeys` call, restoring any that have recovered so a
+    /// downed region doesn't incur its full timeout on every request forever.
+    pub fn spawn_health_check(self: &Arc<Self>) {
+        let pool = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS));
+
+            loop {
+                ticker.tick().await;
+
+                for (index, entry) in pool.entries.iter().enumerate() {
+                    if entry.healthy.load(Ordering::Relaxed) {
+                        continue;
+                    }
+
+                    let probe = tokio::time::timeout(
+                        pool.attempt_timeout,
+                        entry.client.list_keys().limit(1).send(),
+                    )
+                    .await;
+
+                    if matches!(probe, Ok(Ok(_))) {
+                        pool.mark_healthy(index);
+                    } else {
+                        debug!(region = %entry.region, "KMS region still unhealthy");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Builds an `AwsSigner` for `key_id`, trying regions in priority order
+    /// (healthy regions first) and requiring the first `quorum` reachable
+    /// regions to agree on the resulting address before returning the signer
+    /// from the highest-priority one that responded.
+    pub async fn signer(&self, key_id: &str, chain_id: Option<u64>) -> AnyhowResult<AwsSigner> {
+        let mut agreed: Option<AwsSigner> = None;
+        let mut confirmations = 0usize;
+
+        for index in self.try_order() {
+            let entry = &self.entries[index];
+            let attempt = tokio::time::timeout(
+                self.attempt_timeout,
+                AwsSigner::new(entry.client.clone(), key_id.to_string(), chain_id),
+            )
+            .await;
+
+            let signer = match attempt {
+                Ok(Ok(signer)) => {
+                    self.mark_healthy(index);
+                    signer
+                }
+                _ => {
+                    self.mark_unhealthy(index);
+                    continue;
+                }
+            };
+
+            match &agreed {
+                None => {
+                    info!(region = %entry.region, key_id, "KMS region served signing request");
+                    agreed = Some(signer);
+                    confirmations = 1;
+                }
+                Some(first) if first.address() == signer.address() => {
+                    confirmations += 1;
+                }
+                Some(_) => {
+                    return Err(anyhow!(
+                        "KMS region {} disagrees with the primary region on the address for key {key_id}",
+                        entry.region
+                    ));
+                }
+            }
+
+            if confirmations >= self.quorum {
+                break;
+            }
+        }
+
+        match agreed {
+            Some(signer) if confirmations >= self.quorum => Ok(signer),
+            Some(_) => Err(anyhow!(
+                "only {confirmations}/{} required KMS regions agreed on key {key_id}",
+                self.quorum
+            )),
+            None => Err(anyhow!("all KMS regions are unavailable")),
+        }
+    }
+
+    /// Fetches the DER-encoded SPKI public key for `key_id`, failing over
+    /// across regions (healthy regions first) without requiring quorum
+    /// agreement.
+    pub async fn get_public_key_der(&self, key_id: &str) -> AnyhowResult<Vec<u8>> {
+        for index in self.try_order() {
+            let entry = &self.entries[index];
+            let attempt = tokio::time::timeout(
+                self.attempt_timeout,
+                entry.client.get_public_key().key_id(key_id).send(),
+            )
+            .await;
+
+            if let Ok(Ok(response)) = attempt {
+                if let Some(key) = response.public_key() {
+                    self.mark_healthy(index);
+                    return Ok(key.as_ref().to_vec());
+                }
+            }
+
+            self.mark_unhealthy(index);
+        }
+
+        Err(anyhow!("all KMS regions are unavailable"))
+    }
+}
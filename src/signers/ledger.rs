@@ -0,0 +1,153 @@
+use crate::app_types::{AppJson, JsonRpcBody};
+use crate::jsonrpc::{AddressResponse, JsonRpcReplyBatch, JsonRpcRequest, JsonRpcRequestBatch};
+use crate::shutdown_signal::shutdown_signal;
+use crate::signers::common::{handle_eth_sign_jsonrpc_batch, signer_unavailable_reply};
+use alloy::signers::{
+    ledger::{HDPath, LedgerSigner},
+    Signer,
+};
+use anyhow::Result as AnyhowResult;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Json;
+use axum::{
+    debug_handler,
+    extract::{Path, State},
+    routing::post,
+    Router,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+use std::{collections::HashMap, sync::Arc};
+use structopt::StructOpt;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tower_http::timeout::TimeoutLayer;
+use tower_http::trace::TraceLayer;
+use tracing::debug;
+
+const API_TIMEOUT_SECS: u64 = 30;
+
+/// This backend is the one Ledger signer in the proxy: it's wired in as its
+/// own top-level `Command::Ledger`, mirroring how `Yubihsm`/`AwsKms` each get
+/// a variant rather than nesting under one another's subcommand, and its
+/// routes follow the `/key/:key_id/...` shape `YubiOpt`/`AwsOpt` already use
+/// so the three backends stay interchangeable from a client's point of view.
+/// A derivation path or LedgerLive index is accepted as `key_id` the same way
+/// a YubiHSM key label or KMS key id would be.
+#[derive(StructOpt)]
+pub struct LedgerOpt {
+    /// EIP-155 chain id to bind signatures to
+    #[structopt(long = "chain-id", env = "LEDGER_CHAIN_ID")]
+    pub chain_id: Option<u64>,
+
+    #[structopt(subcommand)] // Note that we mark a field as a subcommand
+    pub cmd: LedgerCommand,
+}
+
+#[derive(StructOpt)]
+pub enum LedgerCommand {
+    Serve,
+}
+
+#[derive(Clone)]
+struct AppState {
+    chain_id: Option<u64>,
+    signers: Arc<Mutex<HashMap<String, LedgerSigner>>>,
+}
+
+/// Keys are addressed by HD derivation path (`m/44'/60'/0'/0/0`) or, if the
+/// path parses as a plain integer, by LedgerLive account index.
+fn parse_hd_path(key_id: &str) -> HDPath {
+    match key_id.parse::<usize>() {
+        Ok(index) => HDPath::LedgerLive(index),
+        Err(_) => HDPath::Other(key_id.to_string()),
+    }
+}
+
+#[debug_handler]
+async fn handle_request(
+    Path(key_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    JsonRpcBody(batch): JsonRpcBody<JsonRpcRequestBatch<Vec<Value>>>,
+) -> AppJson<JsonRpcReplyBatch<Value>> {
+    let signer = match get_signer(state.clone(), key_id).await {
+        Ok(signer) => signer,
+        Err(err) => return signer_unavailable_reply(&batch, &err),
+    };
+
+    handle_eth_sign_jsonrpc_batch(batch, signer, None).await
+}
+
+async fn get_signer(state: Arc<AppState>, key_id: String) -> AnyhowResult<LedgerSigner> {
+    let mut signers = state.signers.lock().await;
+
+    if let Some(signer) = signers.get(&key_id) {
+        return Ok(signer.clone());
+    }
+
+    let signer = LedgerSigner::new(parse_hd_path(&key_id), state.chain_id).await?;
+
+    signers.insert(key_id, signer.clone());
+
+    Ok(signer)
+}
+
+#[debug_handler]
+async fn handle_address_request(
+    Path(key_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    AppJson(_payload): AppJson<JsonRpcRequest<Vec<Value>>>,
+) -> Result<Json<AddressResponse>, StatusCode> {
+    match get_signer(state.clone(), key_id).await {
+        Ok(signer) => Ok(Json(AddressResponse {
+            address: signer.address().to_string(),
+        })),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+#[debug_handler]
+async fn handle_app_version_request(
+    Path(key_id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Value>, StatusCode> {
+    let signer = get_signer(state.clone(), key_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (major, minor, patch) = signer
+        .version()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({ "version": format!("{major}.{minor}.{patch}") })))
+}
+
+pub async fn handle_ledger(opt: LedgerOpt) {
+    match opt.cmd {
+        LedgerCommand::Serve => {
+            let shared_state = Arc::new(AppState {
+                chain_id: opt.chain_id,
+                signers: Arc::new(Mutex::new(HashMap::new())),
+            });
+
+            let app = Router::new()
+                .route("/key/:key_id", post(handle_request))
+                .route("/key/:key_id/address", get(handle_address_request))
+                .route("/key/:key_id/app-version", get(handle_app_version_request))
+                .with_state(shared_state)
+                .layer((
+                    TraceLayer::new_for_http(),
+                    TimeoutLayer::new(Duration::from_secs(API_TIMEOUT_SECS)),
+                ));
+
+            let listener = TcpListener::bind("0.0.0.0:5000").await.unwrap();
+            debug!("listening on {}", listener.local_addr().unwrap());
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await
+                .unwrap();
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::signers::yubihsm::AppState;
 use alloy::primitives::hex;
-use alloy::{network::EthereumWallet, signers::local::yubihsm::Domain, signers::local::YubiSigner};
+use alloy::{signers::local::yubihsm::Domain, signers::local::YubiSigner};
 use anyhow::Result as AnyhowResult;
 
 use std::sync::Arc;
@@ -35,16 +35,15 @@ pub async fn add_mock_wallets(
 
     for (key_id, private_key, _address) in keys_to_use {
         let yubi_signer = YubiSigner::from_key(
-            state.connector.clone(),
+            state.connector_pool.connector(0),
             state.credentials.clone(),
             key_id,
             "".into(),
             Domain::all(),
             private_key,
         )?;
-        let eth_signer = EthereumWallet::from(yubi_signer);
 
-        signers.insert(key_id, eth_signer.clone());
+        signers.insert(key_id, yubi_signer);
     }
 
     Ok(())
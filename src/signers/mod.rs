@@ -0,0 +1,8 @@
+pub mod aws_kms;
+pub mod common;
+pub mod connector_pool;
+pub mod kms_pool;
+pub mod ledger;
+pub mod mock;
+pub mod rpc_fill;
+pub mod yubihsm;
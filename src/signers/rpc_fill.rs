@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use alloy::{
+    network::TransactionBuilder,
+    primitives::{Address, TxHash},
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::TransactionRequest,
+    transports::http::{Client, Http},
+};
+use anyhow::Result as AnyhowResult;
+use tokio::sync::{Mutex, OnceCell};
+
+/// Multiplier/cap knobs for the gas-oracle stage of `fill`, so operators can
+/// pad fee estimates for faster inclusion without letting a fee spike on the
+/// upstream chain blow through a budget.
+#[derive(Clone, Copy, Debug)]
+pub struct GasOracleConfig {
+    pub multiplier: f64,
+    pub cap: Option<u128>,
+}
+
+/// Optional upstream RPC used to auto-fill `chainId`, `nonce` and gas fields
+/// a caller left unset on a `TransactionRequest`, mirroring the
+/// nonce-manager/gas-oracle middleware stack of an ethers-style provider.
+/// Fields the caller did supply are never overwritten, except `from`, which
+/// is always set to the signing key's address.
+pub struct RpcFiller {
+    provider: RootProvider<Http<Client>>,
+    chain_id: OnceCell<u64>,
+    /// Per-address nonce manager: once primed from the chain, nonces are
+    /// handed out from this in-memory counter instead of being refetched for
+    /// every `eth_sendTransaction`, so concurrent sends from the same key
+    /// don't race on `eth_getTransactionCount`.
+    nonces: Mutex<HashMap<Address, u64>>,
+    /// Gates the gas/fee estimation stage of `fill`: when `Some`, missing
+    /// gas limit and fee fields are estimated via the upstream RPC and
+    /// scaled by this config's multiplier/cap; when `None`, `fill` only
+    /// fills `chainId`/`nonce` and transactions must carry their own gas
+    /// fields.
+    gas_oracle: Option<GasOracleConfig>,
+}
+
+impl RpcFiller {
+    pub fn new(rpc_url: &str, gas_oracle: Option<GasOracleConfig>) -> AnyhowResult<Self> {
+        let provider = ProviderBuilder::new().on_http(rpc_url.parse()?);
+
+        Ok(Self {
+            provider,
+            chain_id: OnceCell::new(),
+            nonces: Mutex::new(HashMap::new()),
+            gas_oracle,
+        })
+    }
+
+    /// Returns the next nonce to use for `from`, priming the counter from
+    /// the chain's pending transaction count the first time it's asked.
+    pub async fn next_nonce(&self, from: Address) -> AnyhowResult<u64> {
+        let mut nonces = self.nonces.lock().await;
+
+        if let Some(nonce) = nonces.get_mut(&from) {
+            let next = *nonce;
+            *nonce += 1;
+            return Ok(next);
+        }
+
+        let nonce = self.provider.get_transaction_count(from).pending().await?;
+        nonces.insert(from, nonce + 1);
+
+        Ok(nonce)
+    }
+
+    /// Returns a nonce handed out by `next_nonce` that was never successfully
+    /// broadcast, so it can be reissued instead of leaving a permanent gap.
+    /// Only rewinds the counter if nothing else has since taken a later
+    /// nonce, so concurrent sends from the same key never regress.
+    pub async fn release_nonce(&self, from: Address, nonce: u64) {
+        let mut nonces = self.nonces.lock().await;
+
+        if nonces.get(&from) == Some(&(nonce + 1)) {
+            nonces.insert(from, nonce);
+        }
+    }
+
+    /// Re-primes the nonce counter for `from` from the chain, for use after a
+    /// "nonce too low" rejection desyncs the in-memory counter.
+    pub async fn resync_nonce(&self, from: Address) -> AnyhowResult<()> {
+        let nonce = self.provider.get_transaction_count(from).pending().await?;
+        self.nonces.lock().await.insert(from, nonce);
+
+        Ok(())
+    }
+
+    pub async fn send_raw_transaction(&self, encoded_tx: &[u8]) -> AnyhowResult<TxHash> {
+        let pending = self.provider.send_raw_transaction(encoded_tx).await?;
+
+        Ok(*pending.tx_hash())
+    }
+
+    pub async fn fill(&self, from: Address, tx: &mut TransactionRequest) -> AnyhowResult<()> {
+        tx.set_from(from);
+
+        if tx.chain_id().is_none() {
+            let chain_id = self
+                .chain_id
+                .get_or_try_init(|| self.provider.get_chain_id())
+                .await?;
+            tx.set_chain_id(*chain_id);
+        }
+
+        if tx.nonce().is_none() {
+            let nonce = self.provider.get_transaction_count(from).pending().await?;
+            tx.set_nonce(nonce);
+        }
+
+        if self.gas_oracle.is_some() {
+            if tx.gas_limit().is_none() {
+                let gas = self.provider.estimate_gas(&tx.clone()).await?;
+                tx.set_gas_limit(self.scale_gas_limit(gas as u128) as u64);
+            }
+
+            let has_1559_fee =
+                tx.max_fee_per_gas().is_some() || tx.max_priority_fee_per_gas().is_some();
+
+            if !has_1559_fee && tx.gas_price().is_none() {
+                match self.provider.estimate_eip1559_fees(None).await {
+                    Ok(estimate) => {
+                        tx.set_max_fee_per_gas(self.scale_fee(estimate.max_fee_per_gas));
+                        tx.set_max_priority_fee_per_gas(
+                            self.scale_fee(estimate.max_priority_fee_per_gas),
+                        );
+                    }
+                    Err(_) => {
+                        let gas_price = self.provider.get_gas_price().await?;
+                        tx.set_gas_price(self.scale_fee(gas_price));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the configured gas-oracle multiplier to an estimated gas
+    /// limit. No cap: `gas_cap` is wei-denominated and bounds fee spend, not
+    /// the unrelated (much smaller) gas-unit quantity a limit is measured in.
+    fn scale_gas_limit(&self, estimate: u128) -> u128 {
+        let Some(config) = self.gas_oracle else {
+            return estimate;
+        };
+
+        (estimate as f64 * config.multiplier).round() as u128
+    }
+
+    /// Applies the configured gas-oracle multiplier/cap to an estimated fee
+    /// value (wei); a no-op when `--gas-oracle` wasn't enabled.
+    fn scale_fee(&self, estimate: u128) -> u128 {
+        let Some(config) = self.gas_oracle else {
+            return estimate;
+        };
+
+        let scaled = (estimate as f64 * config.multiplier).round() as u128;
+
+        match config.cap {
+            Some(cap) => scaled.min(cap),
+            None => scaled,
+        }
+    }
+}
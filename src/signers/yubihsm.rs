@@ -1,9 +1,13 @@
-use crate::app_types::{AppJson, AppResult};
-use crate::jsonrpc::{AddressResponse, JsonRpcReply, JsonRpcRequest};
+use crate::app_types::{AppJson, JsonRpcBody};
+use crate::jsonrpc::{
+    AddressResponse, JsonRpcReply, JsonRpcReplyBatch, JsonRpcRequest, JsonRpcRequestBatch,
+    JsonRpcResult, RpcError,
+};
 use crate::shutdown_signal::shutdown_signal;
-use crate::signers::common::handle_eth_sign_jsonrpc;
+use crate::signers::common::{handle_eth_sign_jsonrpc_batch, signer_unavailable_reply};
+use crate::signers::connector_pool::ConnectorPool;
+use crate::signers::rpc_fill::{GasOracleConfig, RpcFiller};
 use alloy::{
-    network::EthereumWallet,
     primitives::Address,
     signers::local::{
         yubihsm::{
@@ -13,7 +17,7 @@ use alloy::{
         YubiSigner,
     },
 };
-use anyhow::Result as AnyhowResult;
+use anyhow::{anyhow, Result as AnyhowResult};
 use axum::http::StatusCode;
 use axum::routing::get;
 use axum::Json;
@@ -60,17 +64,25 @@ pub struct YubiOpt {
     )]
     pub device_serial_id: Option<String>,
 
-    /// YubiHSM HTTP address (for HTTP mode)
+    /// YubiHSM HTTP address(es) (for HTTP mode). Repeat the flag or pass a
+    /// comma-separated list to configure a failover pool of connectors, in
+    /// priority order, paired positionally with `--port`
     #[structopt(
         long = "addr",
         env = "YUBIHSM_HTTP_ADDRESS",
-        required_if("mode", "http")
+        required_if("mode", "http"),
+        use_delimiter = true
     )]
-    pub http_address: Option<String>,
+    pub http_addresses: Vec<String>,
 
-    /// YubiHSM HTTP port (for HTTP mode)
-    #[structopt(long = "port", env = "YUBIHSM_HTTP_PORT", required_if("mode", "http"))]
-    pub http_port: Option<u16>,
+    /// YubiHSM HTTP port(s) (for HTTP mode), paired positionally with `--addr`
+    #[structopt(
+        long = "port",
+        env = "YUBIHSM_HTTP_PORT",
+        required_if("mode", "http"),
+        use_delimiter = true
+    )]
+    pub http_ports: Vec<u16>,
 
     /// YubiHSM auth key ID
     #[structopt(short, long = "auth-key", env = "YUBIHSM_AUTH_KEY_ID")]
@@ -80,6 +92,24 @@ pub struct YubiOpt {
     #[structopt(short, long = "pass", env = "YUBIHSM_PASSWORD", hide_env_values = true)]
     pub password: String,
 
+    /// Upstream JSON-RPC URL used to auto-fill nonce and chainId (and, with
+    /// `--gas-oracle`, gas fields) on transactions that leave them unset
+    #[structopt(long = "rpc-url", env = "YUBIHSM_RPC_URL")]
+    pub rpc_url: Option<String>,
+
+    /// Auto-fill missing gas/fee fields from the upstream RPC's
+    /// `eth_estimateGas`/`eth_feeHistory`-backed estimates before signing
+    #[structopt(long = "gas-oracle")]
+    pub gas_oracle: bool,
+
+    /// Multiplier applied to gas-oracle estimates, to pad for inclusion
+    #[structopt(long = "gas-multiplier", default_value = "1.0")]
+    pub gas_multiplier: f64,
+
+    /// Upper bound applied to gas-oracle fee estimates (wei), to bound spend
+    #[structopt(long = "gas-cap")]
+    pub gas_cap: Option<u128>,
+
     #[structopt(subcommand)] // Note that we mark a field as a subcommand
     pub cmd: YubiCommand,
 }
@@ -99,35 +129,102 @@ pub enum YubiCommand {
 
 #[derive(Clone)]
 struct AppState {
-    connector: Connector,
+    connector_pool: Arc<ConnectorPool>,
     credentials: Credentials,
-    signers: Arc<Mutex<HashMap<u16, EthereumWallet>>>,
+    /// The connector the cached signers below were built against; cleared
+    /// whenever failover moves to a different connector so stale sessions
+    /// against the dead connector aren't served from cache.
+    active_connector: Arc<Mutex<Option<usize>>>,
+    signers: Arc<Mutex<HashMap<u16, YubiSigner>>>,
+    rpc_filler: Option<Arc<RpcFiller>>,
 }
 
 #[debug_handler]
 async fn handle_request(
     Path(key_id): Path<u16>,
     State(state): State<Arc<AppState>>,
-    AppJson(payload): AppJson<JsonRpcRequest<Vec<Value>>>,
-) -> AppResult<JsonRpcReply<Value>> {
-    let eth_signer = get_signer(state.clone(), key_id).await?;
-    handle_eth_sign_jsonrpc(payload, eth_signer).await
+    JsonRpcBody(batch): JsonRpcBody<JsonRpcRequestBatch<Vec<Value>>>,
+) -> AppJson<JsonRpcReplyBatch<Value>> {
+    let signer = match get_signer(state.clone(), key_id).await {
+        Ok(signer) => signer,
+        Err(err) => return signer_unavailable_reply(&batch, &err),
+    };
+
+    let reply = handle_eth_sign_jsonrpc_batch(batch, signer, state.rpc_filler.as_deref()).await;
+
+    if batch_has_hsm_error(&reply.0) {
+        invalidate_active_connector(&state).await;
+    }
+
+    reply
+}
+
+/// An `HSM_ERROR` surfacing from the dispatch path above means a direct
+/// `sign_hash` call against the cached `YubiSigner` failed, so the connector
+/// behind it is no longer trustworthy. This is narrower than `SIGNER_ERROR`,
+/// which also covers downstream RPC-filler/tx-building failures (a reverting
+/// `eth_estimateGas`, a malformed transaction) that say nothing about the
+/// HSM connector's health and shouldn't trigger failover.
+fn batch_has_hsm_error(batch: &JsonRpcReplyBatch<Value>) -> bool {
+    let is_hsm_error = |reply: &JsonRpcReply<Value>| {
+        matches!(&reply.result, JsonRpcResult::Error { code, .. } if *code == RpcError::HSM_ERROR)
+    };
+
+    match batch {
+        JsonRpcReplyBatch::Single(reply) => is_hsm_error(reply),
+        JsonRpcReplyBatch::Batch(replies) => replies.iter().any(is_hsm_error),
+    }
 }
 
-async fn get_signer(state: Arc<AppState>, key_id: u16) -> AnyhowResult<EthereumWallet> {
+/// Marks the currently active connector unhealthy and drops every cached
+/// signer built against it, so the next request fails over via `try_order`
+/// instead of continuing to hand out a signer behind a dead connector.
+async fn invalidate_active_connector(state: &Arc<AppState>) {
+    let mut active_connector = state.active_connector.lock().await;
+
+    if let Some(index) = active_connector.take() {
+        state.connector_pool.mark_unhealthy(index);
+        state.signers.lock().await.clear();
+    }
+}
+
+async fn get_signer(state: Arc<AppState>, key_id: u16) -> AnyhowResult<YubiSigner> {
+    let mut active_connector = state.active_connector.lock().await;
     let mut signers = state.signers.lock().await;
 
-    if let Some(signer) = signers.get(&key_id) {
-        return Ok(signer.clone());
+    if let Some(index) = *active_connector {
+        if state.connector_pool.is_healthy(index) {
+            if let Some(signer) = signers.get(&key_id) {
+                return Ok(signer.clone());
+            }
+        } else {
+            signers.clear();
+            *active_connector = None;
+        }
     }
 
-    let yubi_signer =
-        YubiSigner::connect(state.connector.clone(), state.credentials.clone(), key_id)?;
-    let eth_signer = EthereumWallet::from(yubi_signer);
+    for index in state.connector_pool.try_order(*active_connector) {
+        let connector = state.connector_pool.connector(index);
 
-    signers.insert(key_id, eth_signer.clone());
+        match YubiSigner::connect(connector, state.credentials.clone(), key_id) {
+            Ok(yubi_signer) => {
+                if *active_connector != Some(index) {
+                    signers.clear();
+                    *active_connector = Some(index);
+                }
+
+                state.connector_pool.mark_healthy(index);
+                signers.insert(key_id, yubi_signer.clone());
+
+                return Ok(yubi_signer);
+            }
+            Err(_) => {
+                state.connector_pool.mark_unhealthy(index);
+            }
+        }
+    }
 
-    Ok(eth_signer)
+    Err(anyhow!("all YubiHSM connectors are unavailable"))
 }
 
 #[debug_handler]
@@ -136,21 +233,14 @@ async fn handle_address_request(
     State(state): State<Arc<AppState>>,
     AppJson(_payload): AppJson<JsonRpcRequest<Vec<Value>>>,
 ) -> Result<Json<AddressResponse>, StatusCode> {
-    match get_address(state.clone(), key_id).await {
-        Ok(address) => Ok(Json(AddressResponse {
-            address: address.to_string(),
+    match get_signer(state.clone(), key_id).await {
+        Ok(signer) => Ok(Json(AddressResponse {
+            address: signer.address().to_string(),
         })),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
-async fn get_address(state: Arc<AppState>, key_id: u16) -> AnyhowResult<Address> {
-    let yubi_signer =
-        YubiSigner::connect(state.connector.clone(), state.credentials.clone(), key_id)?;
-
-    Ok(yubi_signer.address())
-}
-
 fn generate_new_key(
     connector: Connector,
     credentials: Credentials,
@@ -175,7 +265,7 @@ fn generate_new_key(
     Ok((id, signer.address()))
 }
 
-fn create_connector(opt: &YubiOpt) -> Connector {
+fn create_connectors(opt: &YubiOpt) -> Vec<Connector> {
     match opt.mode {
         YubiMode::Usb => {
             let serial = SerialNumber::from_str(
@@ -184,37 +274,65 @@ fn create_connector(opt: &YubiOpt) -> Connector {
                     .expect("USB mode requires a device serial ID"),
             )
             .unwrap();
-            Connector::usb(&UsbConfig {
+
+            vec![Connector::usb(&UsbConfig {
                 serial: Some(serial),
                 timeout_ms: DEFAULT_USB_TIMEOUT_MS,
-            })
+            })]
         }
         YubiMode::Http => {
-            let addr = opt
-                .http_address
-                .as_ref()
-                .expect("HTTP mode requires an address")
-                .clone();
-            let port = *opt.http_port.as_ref().expect("HTTP mode requires a port");
-            Connector::http(&HttpConfig {
-                addr,
-                port,
-                timeout_ms: DEFAULT_HTTP_TIMEOUT_MS,
-            })
+            assert_eq!(
+                opt.http_addresses.len(),
+                opt.http_ports.len(),
+                "HTTP mode requires one --port per --addr"
+            );
+            assert!(
+                !opt.http_addresses.is_empty(),
+                "HTTP mode requires at least one --addr/--port pair"
+            );
+
+            opt.http_addresses
+                .iter()
+                .zip(opt.http_ports.iter())
+                .map(|(addr, port)| {
+                    Connector::http(&HttpConfig {
+                        addr: addr.clone(),
+                        port: *port,
+                        timeout_ms: DEFAULT_HTTP_TIMEOUT_MS,
+                    })
+                })
+                .collect()
         }
     }
 }
 
 pub async fn handle_yubihsm(opt: YubiOpt) {
-    let connector = create_connector(&opt);
+    let connectors = create_connectors(&opt);
     let credentials = Credentials::from_password(opt.auth_key_id, opt.password.as_bytes());
 
     match opt.cmd {
         YubiCommand::Serve => {
+            let gas_oracle = opt.gas_oracle.then_some(GasOracleConfig {
+                multiplier: opt.gas_multiplier,
+                cap: opt.gas_cap,
+            });
+            let rpc_filler = opt
+                .rpc_url
+                .as_deref()
+                .map(|rpc_url| RpcFiller::new(rpc_url, gas_oracle))
+                .transpose()
+                .unwrap()
+                .map(Arc::new);
+
+            let connector_pool = Arc::new(ConnectorPool::new(connectors, credentials.clone()));
+            connector_pool.spawn_health_check();
+
             let shared_state = Arc::new(AppState {
-                connector,
+                connector_pool,
                 credentials,
+                active_connector: Arc::new(Mutex::new(None)),
                 signers: Arc::new(Mutex::new(HashMap::new())),
+                rpc_filler,
             });
 
             let app = Router::new()
@@ -235,7 +353,7 @@ pub async fn handle_yubihsm(opt: YubiOpt) {
         }
         YubiCommand::GenerateKey { label, exportable } => {
             let (id, address) =
-                generate_new_key(connector, credentials, label, exportable).unwrap();
+                generate_new_key(connectors[0].clone(), credentials, label, exportable).unwrap();
 
             println!("Key ID: {}", id);
             println!("Address: {}", address);